@@ -0,0 +1,17 @@
+//! Parser for implementing virtual terminal emulators
+//!
+//! This crate currently exposes a table-driven UTF-8 decoder; it is built
+//! `#![no_std]` so it can be embedded in kernels, WASM targets, and other
+//! bare-metal consumers that have no allocator or `std` available. Tests
+//! link `std` back in, since it's only the test harness that needs it.
+#![no_std]
+
+#[cfg(test)]
+extern crate std;
+
+pub mod utf8;
+
+pub use utf8::{
+    Parser, Receiver, ErrorKind, DecodeError, decode, PrevError, decode_prev, CodePoint,
+    Wtf8Receiver,
+};
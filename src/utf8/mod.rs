@@ -3,13 +3,37 @@
 //! This module implements a table-driven UTF-8 parser which should
 //! theoretically contain the minimal number of branches (1). The only branch is
 //! on the `Action` returned from unpacking a transition.
-use std::char;
+use core::char;
 
 mod types;
 use self::types::{State, Action, unpack};
 
 mod table;
-use self::table::TRANSITIONS;
+use self::table::{TRANSITIONS, WTF8_TRANSITIONS};
+
+/// Why a byte sequence was rejected as invalid UTF-8.
+///
+/// Each variant carries the byte that triggered the rejection so callers can
+/// build precise diagnostics without re-scanning the input.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The byte can never start a sequence, e.g. `0xC0`, `0xC1`, or
+    /// `0xF5..=0xFF`.
+    InvalidLeadByte(u8),
+
+    /// A continuation byte (`0x80..=0xBF`) was seen in `Ground`, where a
+    /// lead byte or an ASCII byte was expected.
+    UnexpectedContinuationByte(u8),
+
+    /// A new lead byte (or an ASCII byte) arrived before the current
+    /// sequence collected all of its continuation bytes.
+    MissingContinuationByte(u8),
+
+    /// The byte is a continuation byte, but out of range for the lead byte
+    /// that started this sequence -- the sequence is an overlong encoding,
+    /// or would decode to a surrogate or out-of-range codepoint.
+    OverlongEncoding(u8),
+}
 
 /// Handles codepoint and invalid sequence events from the parser.
 pub trait Receiver {
@@ -19,7 +43,41 @@ pub trait Receiver {
     fn codepoint(&mut self, char);
 
     /// Invalid sequence encountered
-    fn invalid_sequence(&mut self);
+    ///
+    /// Called with the reason the sequence was rejected.
+    fn invalid_sequence(&mut self, kind: ErrorKind);
+}
+
+/// A raw Unicode code point in the full `U+0000..=U+10FFFF` range.
+///
+/// Unlike `char`, this includes unpaired surrogates (`U+D800..=U+DFFF`),
+/// which WTF-8 -- the superset of UTF-8 used to round-trip ill-formed
+/// UTF-16 such as Windows filenames -- needs to represent.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CodePoint(u32);
+
+impl CodePoint {
+    /// Recover a validated `char`, or `None` if this code point is an
+    /// unpaired surrogate.
+    pub fn to_char(&self) -> Option<char> {
+        char::from_u32(self.0)
+    }
+}
+
+/// Handles codepoint and invalid sequence events from the WTF-8 parser.
+///
+/// Like `Receiver`, but yields a raw `CodePoint` -- which may be an
+/// unpaired surrogate -- instead of a validated `char`.
+pub trait Wtf8Receiver {
+    /// Code point parsed
+    ///
+    /// Called with the codepoint, which may be a surrogate.
+    fn codepoint(&mut self, CodePoint);
+
+    /// Invalid sequence encountered
+    ///
+    /// Called with the reason the sequence was rejected.
+    fn invalid_sequence(&mut self, kind: ErrorKind);
 }
 
 /// A parser for Utf8 Characters
@@ -28,6 +86,16 @@ pub trait Receiver {
 pub struct Parser {
     point: u32,
     state: State,
+
+    /// Bytes consumed so far by the sequence currently in progress,
+    /// counting its lead byte. Discarded (not added to `valid_up_to`) if
+    /// the sequence turns out to be invalid.
+    seq_len: usize,
+
+    /// Count of input bytes, across every call to `advance`/`advance_lossy`
+    /// since this `Parser` was created, that ended up part of a
+    /// successfully decoded character.
+    valid_up_to: usize,
 }
 
 /// Continuation bytes are masked with this value.
@@ -39,9 +107,25 @@ impl Parser {
         Parser {
             point: 0,
             state: State::Ground,
+            seq_len: 0,
+            valid_up_to: 0,
         }
     }
 
+    /// Number of input bytes fed to this `Parser` so far that were part of
+    /// a successfully decoded character.
+    ///
+    /// Bytes belonging to a sequence that was rejected (including the lead
+    /// byte of that sequence) are never counted. Until the first sequence
+    /// is rejected, this is the offset up to which the input is known to be
+    /// good, so callers can split a buffer there without allocating, much
+    /// like `std::str::Utf8Error::valid_up_to`. The count does not stop at
+    /// a rejected sequence: valid bytes after it are still added, so from
+    /// then on it is a count of good bytes, not an offset into the input.
+    pub fn valid_up_to(&self) -> usize {
+        self.valid_up_to
+    }
+
     pub fn advance<R>(&mut self, receiver: &mut R, byte: u8)
         where R: Receiver
     {
@@ -49,43 +133,557 @@ impl Parser {
         let change = TRANSITIONS[cur][byte as usize];
         let (state, action) = unsafe { unpack(change) };
 
-        self.perform_action(receiver, byte, action);
-        self.state = state;
+        match self.step(byte, action) {
+            Some(Event::Codepoint(point)) => {
+                self.state = state;
+                receiver.codepoint(unsafe { char::from_u32_unchecked(point) });
+            },
+            Some(Event::Invalid(kind)) => {
+                self.state = state;
+                receiver.invalid_sequence(kind);
+
+                // `byte` itself was never part of the rejected sequence --
+                // it just isn't a continuation byte, so it cannot have
+                // continued one. Reprocess it from `Ground` (where `state`
+                // already is, per the transition table) so the character
+                // or error it produces on its own is not silently dropped.
+                if let ErrorKind::MissingContinuationByte(byte) = kind {
+                    self.advance(receiver, byte);
+                }
+            },
+            None => self.state = state,
+        }
     }
 
-    fn perform_action<R>(&mut self, receiver: &mut R, byte: u8, action: Action)
+    /// Like `advance`, but never reports an error to the `Receiver`.
+    ///
+    /// Each rejected sequence is replaced with a single `U+FFFD` codepoint.
+    /// If the sequence was interrupted by a byte that cannot continue it
+    /// (rather than being invalid itself), that byte did not belong to the
+    /// rejected sequence: it is replayed from `Ground` so the character it
+    /// starts is not lost.
+    pub fn advance_lossy<R>(&mut self, receiver: &mut R, byte: u8)
         where R: Receiver
     {
+        let mut lossy = LossyReceiver { inner: receiver, resync: None };
+        self.advance(&mut lossy, byte);
+
+        if let Some(byte) = lossy.resync {
+            self.advance_lossy(receiver, byte);
+        }
+    }
+
+    /// Like `advance`, but decodes WTF-8: three-byte sequences that would
+    /// encode a surrogate (`ED A0..BF ..`) are accepted instead of rejected,
+    /// and `receiver` is handed the raw `CodePoint` -- which may be an
+    /// unpaired surrogate -- rather than a validated `char`.
+    pub fn advance_wtf8<R>(&mut self, receiver: &mut R, byte: u8)
+        where R: Wtf8Receiver
+    {
+        let cur = self.state as usize;
+        let change = WTF8_TRANSITIONS[cur][byte as usize];
+        let (state, action) = unsafe { unpack(change) };
+
+        match self.step(byte, action) {
+            Some(Event::Codepoint(point)) => {
+                self.state = state;
+                receiver.codepoint(CodePoint(point));
+            },
+            Some(Event::Invalid(kind)) => {
+                self.state = state;
+                receiver.invalid_sequence(kind);
+
+                if let ErrorKind::MissingContinuationByte(byte) = kind {
+                    self.advance_wtf8(receiver, byte);
+                }
+            },
+            None => self.state = state,
+        }
+    }
+
+    /// Core transition dispatch shared by `advance` and `advance_wtf8`.
+    ///
+    /// Both tables drive the same `Action` set, and updating `point`/
+    /// `seq_len` is identical either way; the two modes only disagree on
+    /// what a finished codepoint is handed back as (a validated `char` vs.
+    /// a raw `CodePoint` that may be an unpaired surrogate), which is why
+    /// this returns the value rather than calling into `receiver` itself.
+    fn step(&mut self, byte: u8, action: Action) -> Option<Event> {
         match action {
-            Action::InvalidSequence => {
+            Action::InvalidLeadByte => {
                 self.point = 0;
-                receiver.invalid_sequence();
+                self.seq_len = 0;
+                Some(Event::Invalid(ErrorKind::InvalidLeadByte(byte)))
+            },
+            Action::UnexpectedContinuationByte => {
+                self.point = 0;
+                self.seq_len = 0;
+                Some(Event::Invalid(ErrorKind::UnexpectedContinuationByte(byte)))
+            },
+            Action::MissingContinuationByte => {
+                self.point = 0;
+                self.seq_len = 0;
+                Some(Event::Invalid(ErrorKind::MissingContinuationByte(byte)))
+            },
+            Action::OverlongEncoding => {
+                self.point = 0;
+                self.seq_len = 0;
+                Some(Event::Invalid(ErrorKind::OverlongEncoding(byte)))
             },
             Action::EmitByte => {
-                receiver.codepoint(byte as char);
+                self.valid_up_to += 1;
+                Some(Event::Codepoint(byte as u32))
             },
             Action::SetByte1 => {
                 let point = self.point | ((byte & CONTINUATION_MASK) as u32);
-                let c = unsafe { char::from_u32_unchecked(point) };
                 self.point = 0;
+                self.valid_up_to += self.seq_len + 1;
+                self.seq_len = 0;
 
-                receiver.codepoint(c);
+                Some(Event::Codepoint(point))
             },
             Action::SetByte2 => {
                 self.point |= ((byte & CONTINUATION_MASK) as u32) << 6;
+                self.seq_len += 1;
+                None
             },
             Action::SetByte2Top => {
                 self.point |= ((byte & 0b0001_1111) as u32) << 6;
+                self.seq_len = 1;
+                None
             },
             Action::SetByte3 => {
                 self.point |= ((byte & CONTINUATION_MASK) as u32) << 12;
+                self.seq_len += 1;
+                None
             },
             Action::SetByte3Top => {
                 self.point |= ((byte & 0b0000_1111) as u32) << 12;
+                self.seq_len = 1;
+                None
             },
             Action::SetByte4 => {
                 self.point |= ((byte & 0b0000_0111) as u32) << 18;
+                self.seq_len = 1;
+                None
             },
         }
     }
 }
+
+/// Outcome of a single `Parser::step` call, before it has been handed to a
+/// `Receiver` or `Wtf8Receiver` -- which disagree on how a finished
+/// codepoint should be represented, so `step` stops here and lets its two
+/// callers finish the job.
+enum Event {
+    /// A sequence just completed; the wrapped value is the raw code point,
+    /// not yet known to be a valid `char`.
+    Codepoint(u32),
+
+    /// A sequence was rejected; see the wrapped `ErrorKind` for why.
+    Invalid(ErrorKind),
+}
+
+/// Adapts a `Receiver` so that invalid sequences become `U+FFFD` instead of
+/// an `invalid_sequence` callback, and reports back whether the byte that
+/// interrupted the sequence needs to be replayed from `Ground`.
+struct LossyReceiver<'a, R: 'a> {
+    inner: &'a mut R,
+    resync: Option<u8>,
+}
+
+impl<'a, R: Receiver + 'a> Receiver for LossyReceiver<'a, R> {
+    fn codepoint(&mut self, c: char) {
+        self.inner.codepoint(c);
+    }
+
+    fn invalid_sequence(&mut self, kind: ErrorKind) {
+        self.inner.codepoint('\u{FFFD}');
+
+        // `Parser::advance` already replays a `MissingContinuationByte`
+        // byte from `Ground` on its own, so only `OverlongEncoding` needs
+        // handling here: that byte -- while a continuation byte -- is out
+        // of range for the lead byte that started this sequence, and was
+        // never part of its maximal subpart. The WHATWG rules replay it
+        // rather than swallowing it, so the character it starts is not
+        // lost.
+        if let ErrorKind::OverlongEncoding(byte) = kind {
+            self.resync = Some(byte);
+        }
+    }
+}
+
+/// Why `decode` could not return a codepoint.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The leading bytes are an invalid sequence; see the wrapped
+    /// `ErrorKind` for why.
+    Invalid(ErrorKind),
+
+    /// The slice ends before the sequence begun by its first byte is
+    /// complete. This is not necessarily an error -- feed more bytes, if
+    /// any exist, and try again.
+    Incomplete,
+}
+
+/// Captures the single codepoint or error produced by feeding a `Parser` a
+/// handful of bytes, for use by `decode` below.
+///
+/// Latches on the first event reported and ignores the rest: a
+/// `MissingContinuationByte` is immediately followed, within the same
+/// `Parser::advance` call, by `Parser` replaying that byte from `Ground` --
+/// which is of no interest here, since `decode` only ever wants the result
+/// of the *first* sequence in the buffer.
+struct SliceReceiver {
+    result: Option<Result<char, ErrorKind>>,
+}
+
+impl Receiver for SliceReceiver {
+    fn codepoint(&mut self, c: char) {
+        if self.result.is_none() {
+            self.result = Some(Ok(c));
+        }
+    }
+
+    fn invalid_sequence(&mut self, kind: ErrorKind) {
+        if self.result.is_none() {
+            self.result = Some(Err(kind));
+        }
+    }
+}
+
+/// Decode the single codepoint at the front of `buf`.
+///
+/// Returns the decoded `char` (or the reason decoding failed) together with
+/// the number of bytes that made it up; `None` is returned only when `buf`
+/// is empty, in which case the consumed count is `0`. On an invalid lead
+/// byte, exactly one byte is reported consumed, so a caller can skip it and
+/// retry at the next byte. A `MissingContinuationByte` reports only the
+/// bytes up to (not including) the interrupting byte as consumed, since
+/// that byte was never part of the rejected sequence -- a caller advancing
+/// by the returned count will re-decode it on the next call. Never panics.
+///
+/// This is a pull-based counterpart to `Parser::advance`, for callers that
+/// already hold a buffer and want to decode it incrementally without
+/// wiring up a `Receiver`.
+pub fn decode(buf: &[u8]) -> (Option<Result<char, DecodeError>>, usize) {
+    if buf.is_empty() {
+        return (None, 0);
+    }
+
+    let mut parser = Parser::new();
+    let mut receiver = SliceReceiver { result: None };
+    let mut consumed = 0;
+
+    for &byte in buf {
+        consumed += 1;
+        parser.advance(&mut receiver, byte);
+
+        match receiver.result {
+            Some(Ok(c)) => return (Some(Ok(c)), consumed),
+            Some(Err(kind @ ErrorKind::MissingContinuationByte(_))) => {
+                return (Some(Err(DecodeError::Invalid(kind))), consumed - 1);
+            },
+            Some(Err(kind)) => return (Some(Err(DecodeError::Invalid(kind))), consumed),
+            None => continue,
+        }
+    }
+
+    (Some(Err(DecodeError::Incomplete)), consumed)
+}
+
+/// Why `decode_prev` could not return the codepoint ending at `index`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PrevError {
+    /// `index` is `0`, or greater than the length of the buffer; there is
+    /// nothing before it to decode.
+    OutOfRange,
+
+    /// Either 4 continuation bytes in a row (more than any sequence holds)
+    /// were walked over, or the start of the buffer was reached, without
+    /// finding a lead byte; or the lead byte that was found declares a
+    /// sequence length that does not match the number of continuation
+    /// bytes actually present before `index`.
+    Invalid,
+}
+
+/// Decode the codepoint that ends just before `buf[index]`.
+///
+/// Walks backward over continuation bytes (`0b10xxxxxx`) starting at
+/// `index` until a lead byte is found, then decodes the sequence it
+/// starts. Returns the codepoint together with the offset its lead byte
+/// starts at. The backward scan is capped at 4 bytes, the longest a UTF-8
+/// sequence can be; a run of 4 or more continuation bytes, or a lead byte
+/// whose declared length disagrees with the distance walked, is reported
+/// as `PrevError::Invalid`.
+///
+/// Useful for zero-copy "peek the previous character" operations, such as
+/// an editor or text view scrolling backward through a shared buffer.
+pub fn decode_prev(buf: &[u8], index: usize) -> Result<(char, usize), PrevError> {
+    if index == 0 || index > buf.len() {
+        return Err(PrevError::OutOfRange);
+    }
+
+    let mut start = index;
+    loop {
+        if index - start == 4 {
+            return Err(PrevError::Invalid);
+        }
+        if start == 0 {
+            return Err(PrevError::Invalid);
+        }
+        start -= 1;
+        if buf[start] & 0b1100_0000 != 0b1000_0000 {
+            break;
+        }
+    }
+
+    match decode(&buf[start..index]) {
+        (Some(Ok(c)), consumed) if consumed == index - start => Ok((c, start)),
+        _ => Err(PrevError::Invalid),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::{Parser, Receiver, ErrorKind};
+
+    /// Records every codepoint/error a `Parser` reports, in order.
+    struct Recorder {
+        codepoints: Vec<char>,
+        errors: Vec<ErrorKind>,
+    }
+
+    impl Recorder {
+        fn new() -> Recorder {
+            Recorder { codepoints: Vec::new(), errors: Vec::new() }
+        }
+    }
+
+    impl Receiver for Recorder {
+        fn codepoint(&mut self, c: char) {
+            self.codepoints.push(c);
+        }
+
+        fn invalid_sequence(&mut self, kind: ErrorKind) {
+            self.errors.push(kind);
+        }
+    }
+
+    fn feed(bytes: &[u8]) -> Recorder {
+        let mut parser = Parser::new();
+        let mut recorder = Recorder::new();
+        for &byte in bytes {
+            parser.advance(&mut recorder, byte);
+        }
+        recorder
+    }
+
+    #[test]
+    fn invalid_lead_byte() {
+        let recorder = feed(&[0xC0]);
+        assert_eq!(recorder.errors, [ErrorKind::InvalidLeadByte(0xC0)]);
+    }
+
+    #[test]
+    fn unexpected_continuation_byte() {
+        let recorder = feed(&[0x80]);
+        assert_eq!(recorder.errors, [ErrorKind::UnexpectedContinuationByte(0x80)]);
+    }
+
+    #[test]
+    fn missing_continuation_byte() {
+        // 0xE2 starts a 3-byte sequence, but an ASCII byte arrives next.
+        let recorder = feed(&[0xE2, b'A']);
+        assert_eq!(recorder.errors, [ErrorKind::MissingContinuationByte(b'A')]);
+        assert_eq!(recorder.codepoints, ['A']);
+    }
+
+    #[test]
+    fn overlong_encoding() {
+        // 0xE0 must be followed by 0xA0..=0xBF; 0x80 makes this overlong.
+        let recorder = feed(&[0xE0, 0x80]);
+        assert_eq!(recorder.errors, [ErrorKind::OverlongEncoding(0x80)]);
+    }
+
+    #[test]
+    fn valid_sequence_still_emits() {
+        let recorder = feed("héllo".as_bytes());
+        assert_eq!(recorder.codepoints, ['h', 'é', 'l', 'l', 'o']);
+        assert!(recorder.errors.is_empty());
+    }
+
+    fn feed_lossy(bytes: &[u8]) -> std::string::String {
+        let mut parser = Parser::new();
+        let mut recorder = Recorder::new();
+        for &byte in bytes {
+            parser.advance_lossy(&mut recorder, byte);
+        }
+        recorder.codepoints.into_iter().collect()
+    }
+
+    // Expected strings here match what `String::from_utf8_lossy` produces
+    // for the same bytes: each maximal subpart of an ill-formed sequence
+    // becomes its own U+FFFD, rather than one U+FFFD per whole sequence.
+    #[test]
+    fn lossy_overlong_three_byte() {
+        assert_eq!(feed_lossy(&[0xE0, 0x80, b'A']), "\u{FFFD}\u{FFFD}A");
+    }
+
+    #[test]
+    fn lossy_surrogate_three_byte() {
+        assert_eq!(feed_lossy(&[0xED, 0xA0, 0x80]), "\u{FFFD}\u{FFFD}\u{FFFD}");
+    }
+
+    #[test]
+    fn lossy_overlong_four_byte() {
+        assert_eq!(feed_lossy(&[0xF0, 0x80, 0x80, b'A']), "\u{FFFD}\u{FFFD}\u{FFFD}A");
+    }
+
+    #[test]
+    fn lossy_missing_continuation_still_resyncs() {
+        // Unchanged from before the OverlongEncoding fix: a byte that can't
+        // continue the sequence at all is still replayed from Ground.
+        assert_eq!(feed_lossy(&[0xE2, b'A']), "\u{FFFD}A");
+    }
+
+    #[test]
+    fn decode_empty_slice() {
+        assert_eq!(super::decode(&[]), (None, 0));
+    }
+
+    #[test]
+    fn decode_ascii_consumes_one_byte() {
+        assert_eq!(super::decode(b"Ax"), (Some(Ok('A')), 1));
+    }
+
+    #[test]
+    fn decode_multibyte_consumes_whole_sequence() {
+        // 'é' is 0xC3 0xA9; a trailing byte must not be consumed.
+        assert_eq!(super::decode(&[0xC3, 0xA9, b'x']), (Some(Ok('é')), 2));
+    }
+
+    #[test]
+    fn decode_invalid_lead_byte_consumes_one_byte() {
+        use super::DecodeError;
+        assert_eq!(
+            super::decode(&[0xC0, b'x']),
+            (Some(Err(DecodeError::Invalid(ErrorKind::InvalidLeadByte(0xC0)))), 1)
+        );
+    }
+
+    #[test]
+    fn decode_incomplete_sequence_consumes_what_it_has() {
+        use super::DecodeError;
+        // 0xE2 starts a 3-byte sequence; only one continuation byte follows
+        // before the buffer runs out.
+        assert_eq!(super::decode(&[0xE2, 0x82]), (Some(Err(DecodeError::Incomplete)), 2));
+    }
+
+    #[test]
+    fn decode_missing_continuation_excludes_interrupting_byte() {
+        use super::DecodeError;
+        // 0xE2 starts a 3-byte sequence, but an ASCII byte arrives next;
+        // `consumed` must stop short of that byte so a caller re-decodes it
+        // rather than losing it.
+        assert_eq!(
+            super::decode(&[0xE2, b'A']),
+            (Some(Err(DecodeError::Invalid(ErrorKind::MissingContinuationByte(b'A')))), 1)
+        );
+    }
+
+    #[test]
+    fn decode_prev_index_zero_is_out_of_range() {
+        use super::PrevError;
+        assert_eq!(super::decode_prev(b"A", 0), Err(PrevError::OutOfRange));
+    }
+
+    #[test]
+    fn decode_prev_index_past_end_is_out_of_range() {
+        use super::PrevError;
+        assert_eq!(super::decode_prev(b"A", 2), Err(PrevError::OutOfRange));
+    }
+
+    #[test]
+    fn decode_prev_ascii() {
+        assert_eq!(super::decode_prev(b"AB", 2), Ok(('B', 1)));
+    }
+
+    #[test]
+    fn decode_prev_multibyte() {
+        // "é" is 0xC3 0xA9, "x" is ASCII.
+        let buf = [0xC3, 0xA9, b'x'];
+        assert_eq!(super::decode_prev(&buf, 2), Ok(('é', 0)));
+    }
+
+    #[test]
+    fn decode_prev_caps_scan_at_four_bytes() {
+        use super::PrevError;
+        // Five continuation bytes in a row never reach a lead byte within
+        // the 4-byte cap.
+        let buf = [0x80, 0x80, 0x80, 0x80, 0x80];
+        assert_eq!(super::decode_prev(&buf, 5), Err(PrevError::Invalid));
+    }
+
+    #[test]
+    fn decode_prev_length_mismatch_is_invalid() {
+        use super::PrevError;
+        // 0xE2 declares a 3-byte sequence, but only one continuation byte
+        // precedes `index`.
+        let buf = [0xE2, 0x82];
+        assert_eq!(super::decode_prev(&buf, 2), Err(PrevError::Invalid));
+    }
+
+    use super::{CodePoint, Wtf8Receiver};
+
+    /// Records every codepoint/error a `Parser` reports via `advance_wtf8`.
+    struct Wtf8Recorder {
+        codepoints: Vec<CodePoint>,
+        errors: Vec<ErrorKind>,
+    }
+
+    impl Wtf8Recorder {
+        fn new() -> Wtf8Recorder {
+            Wtf8Recorder { codepoints: Vec::new(), errors: Vec::new() }
+        }
+    }
+
+    impl Wtf8Receiver for Wtf8Recorder {
+        fn codepoint(&mut self, c: CodePoint) {
+            self.codepoints.push(c);
+        }
+
+        fn invalid_sequence(&mut self, kind: ErrorKind) {
+            self.errors.push(kind);
+        }
+    }
+
+    fn feed_wtf8(bytes: &[u8]) -> Wtf8Recorder {
+        let mut parser = Parser::new();
+        let mut recorder = Wtf8Recorder::new();
+        for &byte in bytes {
+            parser.advance_wtf8(&mut recorder, byte);
+        }
+        recorder
+    }
+
+    #[test]
+    fn wtf8_accepts_unpaired_surrogate() {
+        // ED A0 80 encodes U+D800, a lone high surrogate; plain UTF-8
+        // rejects this, but WTF-8 round-trips it.
+        let recorder = feed_wtf8(&[0xED, 0xA0, 0x80]);
+        assert!(recorder.errors.is_empty());
+        assert_eq!(recorder.codepoints, [CodePoint(0xD800)]);
+        assert_eq!(recorder.codepoints[0].to_char(), None);
+    }
+
+    #[test]
+    fn wtf8_still_emits_normal_codepoints() {
+        let recorder = feed_wtf8("hé".as_bytes());
+        assert!(recorder.errors.is_empty());
+        assert_eq!(recorder.codepoints[0].to_char(), Some('h'));
+        assert_eq!(recorder.codepoints[1].to_char(), Some('é'));
+    }
+}
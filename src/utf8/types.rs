@@ -0,0 +1,106 @@
+//! Types representing individual states and actions
+
+/// Action to take when receiving a byte
+///
+/// Variants are only ever produced by `unpack`'s `transmute` from the
+/// integer literals baked into `table::TRANSITIONS`/`table::WTF8_TRANSITIONS`,
+/// which the compiler can't see as construction -- hence the blanket allow.
+#[allow(dead_code)]
+#[derive(Debug, Copy, Clone)]
+pub enum Action {
+    /// Lead byte can never start a sequence (0xC0, 0xC1, 0xF5..=0xFF)
+    InvalidLeadByte,
+
+    /// A continuation byte (0x80..=0xBF) was seen in `Ground`, where a lead
+    /// byte or an ASCII byte was expected
+    UnexpectedContinuationByte,
+
+    /// Something other than a continuation byte arrived before a
+    /// multi-byte sequence collected all of its continuation bytes
+    MissingContinuationByte,
+
+    /// The byte continues *some* multi-byte sequence, but is out of range
+    /// for this particular lead byte -- the sequence is an overlong
+    /// encoding, or would decode to a surrogate / out-of-range codepoint
+    OverlongEncoding,
+
+    /// Received valid 7-bit ASCII byte which can be directly emitted.
+    EmitByte,
+
+    /// Set the bottom continuation byte
+    SetByte1,
+
+    /// Set the 2nd-from-last continuation byte
+    SetByte2,
+
+    /// Set the 2nd-from-last byte which is part of a two byte sequence
+    SetByte2Top,
+
+    /// Set the 3rd-from-last continuation byte
+    SetByte3,
+
+    /// Set the 3rd-from-last byte which is part of a three byte sequence
+    SetByte3Top,
+
+    /// Set the top byte of a four byte sequence
+    SetByte4,
+}
+
+/// States the parser can be in.
+///
+/// There is a state for each byte in a multi-byte sequence, plus the states
+/// needed to reject overlong encodings and surrogate code points at the
+/// second byte of a three- or four-byte sequence. The discriminant of each
+/// variant is the row offset into the `table::TRANSITIONS` table.
+///
+/// Like `Action`, variants only ever arrive via `unpack`'s `transmute`, so
+/// they need the same `dead_code` allowance. The `U3_2_e0`-style names
+/// mirror the sequence length, the number of its bytes still to come
+/// (counting the one this state reads), and the lead byte, which reads clearer than the camel-case equivalent -- allowed
+/// rather than renamed.
+#[allow(dead_code)]
+#[allow(non_camel_case_types)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum State {
+    Ground = 0,
+    Tail1,
+    Tail2,
+    Tail3,
+
+    /// Second byte of a three-byte sequence starting with 0xE0; must be in
+    /// 0xA0..=0xBF, otherwise the sequence is an overlong encoding.
+    U3_2_e0,
+
+    /// Second byte of a three-byte sequence starting with 0xED; must be in
+    /// 0x80..=0x9F, otherwise the sequence would encode a surrogate.
+    U3_2_ed,
+
+    /// Second byte of a four-byte sequence starting with 0xF0; must be in
+    /// 0x90..=0xBF, otherwise the sequence is an overlong encoding.
+    U4_3_f0,
+
+    /// Second byte of a four-byte sequence starting with 0xF4; must be in
+    /// 0x80..=0x8F, otherwise the codepoint would be out of Unicode range.
+    U4_3_f4,
+}
+
+/// Unpack a (State, Action) pair from a single transition table byte.
+///
+/// State is stored in the low nibble, Action in the high nibble.
+pub unsafe fn unpack(byte: u8) -> (State, Action) {
+    (
+        ::core::mem::transmute(byte & 0x0f),
+        ::core::mem::transmute(byte >> 4),
+    )
+}
+
+/// Pack a (State, Action) pair into a single transition table byte.
+///
+/// `table::TRANSITIONS`/`table::WTF8_TRANSITIONS` are committed as plain
+/// integer literals rather than generated at build time, so nothing in the
+/// crate calls this; it documents how those literals were derived and is
+/// what a regenerated table would use again.
+#[allow(dead_code)]
+pub fn pack(state: State, action: Action) -> u8 {
+    (state as u8) | ((action as u8) << 4)
+}